@@ -1,4 +1,4 @@
-use super::query::{self, run_query, Supported, OP_TYPE_FIELD_NAME};
+use super::query::{self, Supported, OP_TYPE_FIELD_NAME};
 use super::subscription::{IncrementalJoin, SupportedQuery};
 use crate::db::relational_db::{RelationalDB, Tx};
 use crate::error::DBError;
@@ -8,10 +8,13 @@ use crate::vm::{build_query, TxMode};
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_primitives::TableId;
 use spacetimedb_sats::relation::{DbTable, Header};
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
 use spacetimedb_vm::eval::IterRows;
 use spacetimedb_vm::expr::{Query, QueryCode, QueryExpr, SourceExpr, SourceSet};
 use spacetimedb_vm::rel_ops::RelOps;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
+use std::sync::{Arc, Mutex};
 
 /// A hash for uniquely identifying query execution units,
 /// to avoid recompilation of queries that have an open subscription.
@@ -46,14 +49,437 @@ impl QueryHash {
     pub fn from_string(str: &str) -> Self {
         Self::from_bytes(str.as_bytes())
     }
+
+    /// Compute a canonical hash for `expr`, independent of incidental
+    /// structure, so that semantically-equal subscriptions share a
+    /// `QueryHash`, and thus an [`ExecutionUnit`] and a `CompiledPlanCache` entry.
+    ///
+    /// Unlike [`QueryHash::from_string`] on the raw query text,
+    /// this hashes the already-optimized `QueryExpr`,
+    /// so two queries differing only in whitespace or alias naming
+    /// collapse onto the same plan as soon as the planner binds columns.
+    ///
+    /// For a pure filter pipeline (no join), the `query` stages are also
+    /// sorted before hashing, since a conjunction of predicates is
+    /// commutative: where `WHERE a AND b` and `WHERE b AND a` compile to the
+    /// same two stages in a different order, they hash the same.
+    ///
+    /// Called from [`ExecutionUnit::new`]/[`ExecutionUnit::new_cached`] (see
+    /// [`ExecutionUnit::canonical_hash`]) to recompute the hash an
+    /// `ExecutionUnit` is actually keyed by, since the subscription manager
+    /// outside this module still passes in a hash of the raw, un-planned
+    /// query text.
+    // TODO(perf): two gaps remain even after this is wired up:
+    // - Query shapes that include a join are still hashed in their
+    //   original, un-reordered form. Canonicalizing commutative join
+    //   orderings, analogous to how the rustc query system derives
+    //   structure-independent fingerprints, would require a dedicated
+    //   rewrite pass over `QueryExpr` in the planner, since correctly
+    //   reordering joins (unlike independent filter stages) depends on
+    //   `Query` internals not visible from this module.
+    // - Within a single non-join stage, e.g. `WHERE a AND b` vs
+    //   `WHERE b AND a` compiled to one predicate, conjuncts are not
+    //   reordered: we only sort *between* stages, and the stage itself is
+    //   hashed as an opaque `Debug` string here, since the predicate's
+    //   internal AST (to walk and sort its conjuncts) isn't a type this
+    //   module has visibility into either. Both require changes in the
+    //   planner/`Query` definition, which live outside this module.
+    pub fn from_query_expr(expr: &QueryExpr) -> Self {
+        let source = format!("{:?}", expr.source);
+        let mut stages: Vec<String> = expr.query.iter().map(|op| format!("{op:?}")).collect();
+        let has_join = expr.query.iter().any(|op| matches!(op, Query::IndexJoin(_)));
+        if !has_join {
+            stages.sort_unstable();
+        }
+        Self::from_string(&format!("{source}|{}", stages.join("|")))
+    }
+}
+
+/// A supported aggregate function for a `Supported::Aggregate` execution unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// The numeric width/signedness of a `Supported::Aggregate` execution
+/// unit's aggregated column, so that `SUM`/`AVG` can produce an output value
+/// of the same declared type as the column, rather than always `F64`.
+///
+/// Determined from the first value folded into a group's accumulator
+/// (see [`AggregateAccumulator::new`]), since every row in a well-typed
+/// column shares the same `AlgebraicValue` variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AggregateNumericType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    I128,
+    U128,
+    F32,
+    F64,
+}
+
+impl AggregateNumericType {
+    /// Does this type accumulate exactly, in [`AggregateSum::Int`], rather
+    /// than in [`AggregateSum::Float`]?
+    fn is_integer(self) -> bool {
+        !matches!(self, Self::F32 | Self::F64)
+    }
+
+    fn from_algebraic_value(value: &AlgebraicValue) -> Self {
+        match value {
+            AlgebraicValue::I8(_) => Self::I8,
+            AlgebraicValue::U8(_) => Self::U8,
+            AlgebraicValue::I16(_) => Self::I16,
+            AlgebraicValue::U16(_) => Self::U16,
+            AlgebraicValue::I32(_) => Self::I32,
+            AlgebraicValue::U32(_) => Self::U32,
+            AlgebraicValue::I64(_) => Self::I64,
+            AlgebraicValue::U64(_) => Self::U64,
+            AlgebraicValue::I128(_) => Self::I128,
+            AlgebraicValue::U128(_) => Self::U128,
+            AlgebraicValue::F32(_) => Self::F32,
+            // `F64`, and anything else we don't recognize as a narrower
+            // numeric type, is represented as `F64`.
+            _ => Self::F64,
+        }
+    }
+
+    /// Render `n` back as an `AlgebraicValue` of `self`'s width. Only used
+    /// for `MinMax`'s empty-group placeholder, where exactness doesn't
+    /// matter; `SUM`/`AVG` go through [`Self::from_sum`] instead.
+    fn from_f64(self, n: f64) -> AlgebraicValue {
+        match self {
+            Self::I8 => AlgebraicValue::I8(n as i8),
+            Self::U8 => AlgebraicValue::U8(n as u8),
+            Self::I16 => AlgebraicValue::I16(n as i16),
+            Self::U16 => AlgebraicValue::U16(n as u16),
+            Self::I32 => AlgebraicValue::I32(n as i32),
+            Self::U32 => AlgebraicValue::U32(n as u32),
+            Self::I64 => AlgebraicValue::I64(n as i64),
+            Self::U64 => AlgebraicValue::U64(n as u64),
+            Self::I128 => AlgebraicValue::I128(n as i128),
+            Self::U128 => AlgebraicValue::U128(n as u128),
+            Self::F32 => AlgebraicValue::F32((n as f32).into()),
+            Self::F64 => AlgebraicValue::F64(n.into()),
+        }
+    }
+
+    /// Render `sum` back as an `AlgebraicValue` of `self`'s width, so a
+    /// `SUM`/`AVG` comes back out as the aggregated column's own declared
+    /// type.
+    ///
+    /// For an integer `self`, `sum` is expected to be `AggregateSum::Int`
+    /// (accumulated exactly in `i128`, per [`AggregateSum::zero`]), so the
+    /// only precision lost here is if the true sum itself overflows the
+    /// column's own width — the same as summing that column in SQL. For a
+    /// float `self`, `sum` is expected to be `AggregateSum::Float`.
+    fn from_sum(self, sum: AggregateSum) -> AlgebraicValue {
+        match self {
+            Self::I8 | Self::U8 | Self::I16 | Self::U16 | Self::I32 | Self::U32 | Self::I64 | Self::U64 | Self::I128
+            | Self::U128 => {
+                let n = match sum {
+                    AggregateSum::Int(n) => n,
+                    AggregateSum::Float(f) => f as i128,
+                };
+                match self {
+                    Self::I8 => AlgebraicValue::I8(n as i8),
+                    Self::U8 => AlgebraicValue::U8(n as u8),
+                    Self::I16 => AlgebraicValue::I16(n as i16),
+                    Self::U16 => AlgebraicValue::U16(n as u16),
+                    Self::I32 => AlgebraicValue::I32(n as i32),
+                    Self::U32 => AlgebraicValue::U32(n as u32),
+                    Self::I64 => AlgebraicValue::I64(n as i64),
+                    Self::U64 => AlgebraicValue::U64(n as u64),
+                    Self::I128 => AlgebraicValue::I128(n),
+                    Self::U128 => AlgebraicValue::U128(n as u128),
+                    Self::F32 | Self::F64 => unreachable!("handled above"),
+                }
+            }
+            Self::F32 | Self::F64 => {
+                let f = match sum {
+                    AggregateSum::Int(n) => n as f64,
+                    AggregateSum::Float(f) => f,
+                };
+                match self {
+                    Self::F32 => AlgebraicValue::F32((f as f32).into()),
+                    Self::F64 => AlgebraicValue::F64(f.into()),
+                    _ => unreachable!("handled above"),
+                }
+            }
+        }
+    }
+}
+
+/// A `SUM`/`AVG` accumulator, kept in whichever representation is exact for
+/// the aggregated column: `Int` (`i128`) for integer columns, so large
+/// `I64`/`U64`/`I128`/`U128` values don't lose precision the way `f64` would
+/// past 2^53; `Float` for `F32`/`F64` columns.
+#[derive(Debug, Clone, Copy)]
+enum AggregateSum {
+    Int(i128),
+    Float(f64),
+}
+
+impl AggregateSum {
+    fn zero(numeric_type: AggregateNumericType) -> Self {
+        if numeric_type.is_integer() {
+            Self::Int(0)
+        } else {
+            Self::Float(0.0)
+        }
+    }
+}
+
+/// The running, incrementally-maintained state for a single group of a
+/// `Supported::Aggregate` execution unit.
+///
+/// `row_count` is tracked regardless of `AggregateFunc`,
+/// so that a group can be dropped as soon as its last row is deleted.
+#[derive(Debug, Clone)]
+struct AggregateAccumulator {
+    row_count: u64,
+    /// The declared numeric type of the aggregated column, so `value()` can
+    /// produce a `SUM`/`AVG` result of the same width rather than `F64`.
+    numeric_type: AggregateNumericType,
+    state: AggregateState,
+}
+
+#[derive(Debug, Clone)]
+enum AggregateState {
+    Count,
+    Sum(AggregateSum),
+    /// Accumulates the running sum exactly, same as `Sum`; `AggregateFunc::Avg`
+    /// divides it down by `row_count` in [`AggregateAccumulator::value`].
+    ///
+    /// For an integer column, that division is integer division: it
+    /// truncates toward zero, e.g. `AVG(2, 3)` is `2`, not `2.5`. This
+    /// matches the aggregated column's own type (an `AVG` over an `I32`
+    /// column can only produce an `I32`), and is the same truncation SQL
+    /// engines apply to integer division.
+    Avg(AggregateSum),
+    /// A multiset of the group's values for the aggregated column,
+    /// keyed in sorted order, so that deleting the current min/max
+    /// reveals the next one without rescanning the table.
+    MinMax(BTreeMap<AlgebraicValue, u64>),
+}
+
+impl AggregateAccumulator {
+    /// Create an accumulator for `func`, inferring the aggregated column's
+    /// numeric width from `sample`: the first value folded into this group.
+    fn new(func: AggregateFunc, sample: &AlgebraicValue) -> Self {
+        let numeric_type = AggregateNumericType::from_algebraic_value(sample);
+        let state = match func {
+            AggregateFunc::Count => AggregateState::Count,
+            AggregateFunc::Sum => AggregateState::Sum(AggregateSum::zero(numeric_type)),
+            AggregateFunc::Avg => AggregateState::Avg(AggregateSum::zero(numeric_type)),
+            AggregateFunc::Min | AggregateFunc::Max => AggregateState::MinMax(BTreeMap::new()),
+        };
+        Self {
+            row_count: 0,
+            numeric_type,
+            state,
+        }
+    }
+
+    fn fold_insert(&mut self, value: &AlgebraicValue) {
+        self.row_count += 1;
+        match &mut self.state {
+            AggregateState::Count => {}
+            AggregateState::Sum(sum) | AggregateState::Avg(sum) => match sum {
+                AggregateSum::Int(n) => *n += ExecutionUnit::algebraic_as_i128(value),
+                AggregateSum::Float(f) => *f += ExecutionUnit::algebraic_as_f64(value),
+            },
+            AggregateState::MinMax(multiset) => *multiset.entry(value.clone()).or_insert(0) += 1,
+        }
+    }
+
+    fn fold_delete(&mut self, value: &AlgebraicValue) {
+        self.row_count = self.row_count.saturating_sub(1);
+        match &mut self.state {
+            AggregateState::Count => {}
+            AggregateState::Sum(sum) | AggregateState::Avg(sum) => match sum {
+                AggregateSum::Int(n) => *n -= ExecutionUnit::algebraic_as_i128(value),
+                AggregateSum::Float(f) => *f -= ExecutionUnit::algebraic_as_f64(value),
+            },
+            AggregateState::MinMax(multiset) => {
+                if let Some(count) = multiset.get_mut(value) {
+                    *count -= 1;
+                    if *count == 0 {
+                        multiset.remove(value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The current value of the aggregate, to be appended to the group's output row.
+    ///
+    /// `SUM`/`AVG` are rendered back in `self.numeric_type`, the aggregated
+    /// column's own declared type; `COUNT` is always `U64`, since it counts
+    /// rows rather than deriving from the column; `MIN`/`MAX` are exact
+    /// `AlgebraicValue`s taken from the source column and so already carry
+    /// its type.
+    fn value(&self, func: AggregateFunc) -> AlgebraicValue {
+        match (&self.state, func) {
+            (AggregateState::Count, _) => AlgebraicValue::U64(self.row_count),
+            (AggregateState::Sum(sum), _) => self.numeric_type.from_sum(*sum),
+            (AggregateState::Avg(sum), _) => {
+                let avg = if self.row_count == 0 {
+                    AggregateSum::zero(self.numeric_type)
+                } else {
+                    match sum {
+                        AggregateSum::Int(n) => AggregateSum::Int(n / self.row_count as i128),
+                        AggregateSum::Float(f) => AggregateSum::Float(f / self.row_count as f64),
+                    }
+                };
+                self.numeric_type.from_sum(avg)
+            }
+            (AggregateState::MinMax(multiset), AggregateFunc::Max) => multiset
+                .keys()
+                .next_back()
+                .cloned()
+                .unwrap_or_else(|| self.numeric_type.from_f64(0.0)),
+            (AggregateState::MinMax(multiset), _) => multiset
+                .keys()
+                .next()
+                .cloned()
+                .unwrap_or_else(|| self.numeric_type.from_f64(0.0)),
+        }
+    }
+}
+
+/// Sort direction for a `Supported::TopK` execution unit's `ORDER BY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// A row's rank within a `Supported::TopK` window or overflow buffer.
+///
+/// Ordered so that the "best" rows, by the subscription's `ORDER BY`,
+/// are always the *smallest* keys of a `BTreeMap`, regardless of
+/// `SortOrder::Asc`/`SortOrder::Desc`.
+#[derive(Debug, Clone)]
+struct OrderKey {
+    value: AlgebraicValue,
+    order: SortOrder,
+}
+
+impl PartialEq for OrderKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for OrderKey {}
+
+impl PartialOrd for OrderKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let ord = self.value.cmp(&other.value);
+        match self.order {
+            SortOrder::Asc => ord,
+            SortOrder::Desc => ord.reverse(),
+        }
+    }
+}
+
+/// Incrementally-maintained state for a `Supported::TopK` execution unit:
+/// the current top-`limit` rows (the window visible to the subscriber),
+/// plus a bounded buffer of the rows immediately beyond it, so that a
+/// deletion from the window can be backfilled without a full rescan of
+/// the `DbTable`.
+#[derive(Debug, Default)]
+struct TopKState {
+    window: BTreeMap<OrderKey, Vec<ProductValue>>,
+    overflow: BTreeMap<OrderKey, Vec<ProductValue>>,
+}
+
+impl TopKState {
+    fn len(map: &BTreeMap<OrderKey, Vec<ProductValue>>) -> usize {
+        map.values().map(Vec::len).sum()
+    }
+
+    fn push(map: &mut BTreeMap<OrderKey, Vec<ProductValue>>, key: OrderKey, row: ProductValue) {
+        map.entry(key).or_default().push(row);
+    }
+
+    /// Remove and return one row from the bucket at `key`, if any.
+    fn pop_one(map: &mut BTreeMap<OrderKey, Vec<ProductValue>>, key: &OrderKey) -> Option<ProductValue> {
+        let rows = map.get_mut(key)?;
+        let row = rows.pop();
+        if rows.is_empty() {
+            map.remove(key);
+        }
+        row
+    }
+
+    /// Remove a specific `row` from the bucket at `key`, if present.
+    fn remove(map: &mut BTreeMap<OrderKey, Vec<ProductValue>>, key: &OrderKey, row: &ProductValue) -> bool {
+        let Some(rows) = map.get_mut(key) else { return false };
+        if let Some(pos) = rows.iter().position(|r| r == row) {
+            rows.remove(pos);
+            if rows.is_empty() {
+                map.remove(key);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The worst (highest-ranked) key in `map`, if any.
+    fn worst(map: &BTreeMap<OrderKey, Vec<ProductValue>>) -> Option<OrderKey> {
+        map.keys().next_back().cloned()
+    }
+
+    /// The best (lowest-ranked) key in `map`, if any.
+    fn best(map: &BTreeMap<OrderKey, Vec<ProductValue>>) -> Option<OrderKey> {
+        map.keys().next().cloned()
+    }
 }
 
 #[derive(Debug)]
 enum ExecutionUnitQueries {
-    /// For semijoins, store a partially-optimized plan,
-    /// and fully compile and optimize it on every `eval` and `eval_incr`.
-    // TODO(perf, 816): compile once, run repeatedly.
-    Semijoin(QueryExpr),
+    /// For semijoins, store a pre-compiled plan for `eval`,
+    /// along with the [`QueryExpr`] as passed in, used to drive `eval_incr`
+    /// via [`IncrementalJoin`].
+    Semijoin {
+        /// A version of the plan compiled for `eval`,
+        /// whose source is a [`DbTable`].
+        ///
+        /// This is compiled once, in [`ExecutionUnit::new`],
+        /// rather than on every call to `eval`.
+        eval_plan: QueryCode,
+
+        /// The plan as passed to [`ExecutionUnit::new`], handed unchanged to
+        /// [`IncrementalJoin::new`] on every `eval_incr`.
+        ///
+        /// Unlike `eval_plan`, nothing is pre-compiled for `Semijoin`'s
+        /// `eval_incr` path: `IncrementalJoin::new` (in `super::subscription`)
+        /// plans and compiles its own sides against the delta `SourceSet` on
+        /// every call, so each incremental update of a semijoin still pays
+        /// that planning cost.
+        // TODO(perf, 816): see the doc comment above; tracked upstream
+        // against `IncrementalJoin`, not actionable from this module.
+        eval_incr_plan: QueryExpr,
+    },
 
     /// For single-table selects, store two versions of the plan:
     /// one for `eval`, another for `eval_incr`.
@@ -73,6 +499,60 @@ enum ExecutionUnitQueries {
         /// a `MemTable` of row updates, as produced by [`query::to_mem_table_with_op_type`].
         eval_incr_plan: QueryCode,
     },
+
+    /// For grouped aggregates (`COUNT`/`SUM`/`AVG`/`MIN`/`MAX`),
+    /// store the compiled scan of the source table,
+    /// along with the shape of the aggregation itself.
+    ///
+    /// Unlike `Select` and `Semijoin`, the running per-group state for this
+    /// variant is *not* stored here, since it is mutated on every `eval_incr`
+    /// and so cannot be shared across subscriptions via the `CompiledPlanCache`.
+    /// See [`ExecutionUnit::agg_state`].
+    // TODO: unreachable outside of direct construction via
+    // `ExecutionUnit::new_aggregate` (see its doc comment and `kind()`):
+    // `super::query::Supported` has no `Aggregate` variant yet, and the
+    // planner doesn't parse `GROUP BY`/aggregate functions into one of
+    // these units. Both belong in `super::query`, not this module.
+    Aggregate {
+        /// A compiled scan of the source table, used to populate `agg_state` on `eval`.
+        scan_plan: QueryCode,
+        /// The positions, within a source row, of the `GROUP BY` columns.
+        group_cols: Vec<usize>,
+        /// The position, within a source row, of the column being aggregated.
+        agg_col: usize,
+        func: AggregateFunc,
+        /// The `TableId` of this aggregate's own result table.
+        ///
+        /// An aggregate's output rows are shaped `(group_cols..., agg_value)`,
+        /// not the source table's row shape, so they cannot be reported to
+        /// subscribers under the source table's id/`Header` the way `Select`
+        /// and `Semijoin` rows can; see [`ExecutionUnit::return_table`].
+        result_table_id: TableId,
+        /// The `Header` (name and column types) of the aggregate's result
+        /// table, matching the shape of its output rows.
+        result_header: Header,
+    },
+
+    /// For `ORDER BY ... LIMIT n` subscriptions, store the compiled scan of
+    /// the source table, along with the shape of the ordering and window.
+    ///
+    /// As with `Aggregate`, the incrementally-maintained window and overflow
+    /// buffer are not stored here; see [`ExecutionUnit::topk_state`].
+    ///
+    // TODO: unreachable outside of direct construction via
+    // `ExecutionUnit::new_topk` (see its doc comment and `kind()`):
+    // `super::query::Supported` has no `TopK` variant yet, and the planner
+    // doesn't parse `ORDER BY ... LIMIT` into one of these units. Both
+    // belong in `super::query`, not this module.
+    TopK {
+        /// A compiled scan of the source table, used to (re-)populate `topk_state`.
+        scan_plan: QueryCode,
+        /// The position, within a source row, of the `ORDER BY` column.
+        order_col: usize,
+        order: SortOrder,
+        /// The `LIMIT`, i.e. the number of rows in the window.
+        limit: usize,
+    },
 }
 
 /// An atomic unit of execution within a subscription set.
@@ -82,7 +562,125 @@ enum ExecutionUnitQueries {
 #[derive(Debug)]
 pub struct ExecutionUnit {
     hash: QueryHash,
-    queries: ExecutionUnitQueries,
+    /// Shared with other `ExecutionUnit`s for the same [`QueryHash`]
+    /// when constructed via [`ExecutionUnit::new_cached`].
+    queries: Arc<ExecutionUnitQueries>,
+
+    /// Per-group running aggregate state, present only for
+    /// `ExecutionUnitQueries::Aggregate` units; `None` otherwise.
+    ///
+    /// This lives outside of `queries` because it is unique to this
+    /// subscription's `ExecutionUnit` and is mutated under the `Tx` of
+    /// every `eval_incr`, unlike the read-only compiled plan.
+    agg_state: Option<Mutex<HashMap<ProductValue, AggregateAccumulator>>>,
+
+    /// The incrementally-maintained window and overflow buffer, present only
+    /// for `ExecutionUnitQueries::TopK` units; `None` otherwise.
+    /// Lives outside of `queries` for the same reason as `agg_state`.
+    topk_state: Option<Mutex<TopKState>>,
+}
+
+/// The schema version of a single table,
+/// bumped on every DDL change that mutates it.
+type SchemaVersion = u64;
+
+/// A single entry in the [`CompiledPlanCache`]:
+/// a shared, compiled plan, along with the schema version of every
+/// table it was compiled against.
+#[derive(Debug, Clone)]
+struct CachedPlan {
+    queries: Arc<ExecutionUnitQueries>,
+    /// The tables this plan reads, and their schema version as of compilation.
+    versions: Vec<(TableId, SchemaVersion)>,
+}
+
+/// A cache of compiled [`ExecutionUnitQueries`], keyed by [`QueryHash`].
+///
+/// Many subscribers commonly open identical queries,
+/// so rather than compiling and optimizing a plan once per subscriber,
+/// a caller can look up a previously compiled plan here via
+/// [`ExecutionUnit::new_cached`] and share it (via `Arc`) with every
+/// `ExecutionUnit` that has the same hash.
+///
+/// Cache entries are invalidated by schema version rather than by hashing
+/// the full compiled plan: each entry records the [`TableId`]s it reads,
+/// together with a monotonically-increasing version number per table.
+/// Bumping a table's version via [`CompiledPlanCache::bump_schema_version`]
+/// invalidates every cached entry that reads that table,
+/// so the next lookup recompiles (and re-caches) it.
+///
+/// Nothing outside this module calls `bump_schema_version` yet (there's no
+/// DDL hook in this tree to call it from), so a `CompiledPlanCache` that
+/// outlives a schema migration will serve stale compiled plans. Because of
+/// that, [`ExecutionUnit::new`] — the path every existing caller goes
+/// through — deliberately does *not* route through a shared, long-lived
+/// instance of this cache; it compiles its own plan every time, as it did
+/// before this cache existed. Only a caller that also wires up
+/// `bump_schema_version` against its own DDL path should construct a
+/// `CompiledPlanCache` and use [`ExecutionUnit::new_cached`] directly.
+#[derive(Debug, Default)]
+pub struct CompiledPlanCache {
+    entries: Mutex<HashMap<QueryHash, CachedPlan>>,
+    schema_versions: Mutex<HashMap<TableId, SchemaVersion>>,
+}
+
+impl CompiledPlanCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn current_version(&self, table_id: TableId) -> SchemaVersion {
+        *self.schema_versions.lock().unwrap().get(&table_id).unwrap_or(&0)
+    }
+
+    /// Bump the schema version of `table_id`.
+    ///
+    /// Any cached plan reading `table_id` is then invalidated and
+    /// recompiled on its next lookup.
+    // TODO: no DDL hook outside this module calls this yet, so in practice
+    // cache entries are only ever invalidated by process restart. Wiring
+    // this up belongs with wherever `ALTER TABLE`/schema migrations are
+    // executed, which isn't part of this module.
+    pub fn bump_schema_version(&self, table_id: TableId) {
+        let mut versions = self.schema_versions.lock().unwrap();
+        *versions.entry(table_id).or_insert(0) += 1;
+    }
+
+    /// Is `cached`'s recorded schema version stale,
+    /// i.e. has any table it reads been mutated since it was compiled?
+    fn is_stale(&self, cached: &CachedPlan) -> bool {
+        cached
+            .versions
+            .iter()
+            .any(|&(table_id, version)| self.current_version(table_id) != version)
+    }
+
+    /// Look up the compiled plan for `hash`, recompiling and re-caching it if
+    /// absent, or if stale relative to the current schema version of any
+    /// table it reads.
+    fn get_or_compile(&self, plan: SupportedQuery, hash: QueryHash) -> Arc<ExecutionUnitQueries> {
+        if let Some(cached) = self.entries.lock().unwrap().get(&hash) {
+            if !self.is_stale(cached) {
+                return cached.queries.clone();
+            }
+        }
+
+        let table_ids = ExecutionUnit::referenced_table_ids(&plan);
+        let queries = Arc::new(ExecutionUnit::compile(plan));
+        let versions = table_ids
+            .into_iter()
+            .map(|table_id| (table_id, self.current_version(table_id)))
+            .collect();
+
+        self.entries.lock().unwrap().insert(
+            hash,
+            CachedPlan {
+                queries: queries.clone(),
+                versions,
+            },
+        );
+        queries
+    }
 }
 
 /// An ExecutionUnit is uniquely identified by its QueryHash.
@@ -145,8 +743,176 @@ impl ExecutionUnit {
         Self::compile_query_expr_to_query_code(expr)
     }
 
+    /// Pre-compile the `eval` path of a semijoin to `QueryCode`,
+    /// so that `eval` only has to build a query iterator against a fresh
+    /// `SourceSet`, rather than re-planning the join from scratch.
+    fn compile_semijoin_eval(expr: &QueryExpr) -> QueryCode {
+        Self::compile_query_expr_to_query_code(expr.clone())
+    }
+
+    /// Construct an `ExecutionUnit` for `eval_plan`, compiling it fresh.
+    ///
+    /// This does *not* go through a shared [`CompiledPlanCache`]: see the
+    /// note on that type for why. A caller that wants the compiled-plan-reuse
+    /// optimization, and can also guarantee it invalidates the cache on
+    /// schema changes, should use [`Self::new_cached`] directly instead.
+    ///
+    /// `hash` is superseded by [`QueryHash::from_query_expr`] on `eval_plan`;
+    /// see the note on [`Self::canonical_hash`] for why.
     pub fn new(eval_plan: SupportedQuery, hash: QueryHash) -> Self {
-        let queries = match eval_plan {
+        let hash = Self::canonical_hash(&eval_plan, hash);
+        let queries = Arc::new(Self::compile(eval_plan));
+        ExecutionUnit {
+            hash,
+            queries,
+            agg_state: None,
+            topk_state: None,
+        }
+    }
+
+    /// Construct an `ExecutionUnit`, sharing a compiled plan with any other
+    /// `ExecutionUnit` previously constructed for the same `hash`,
+    /// via `cache`, rather than recompiling `eval_plan` from scratch.
+    ///
+    /// `hash` is superseded by [`QueryHash::from_query_expr`] on `eval_plan`;
+    /// see the note on [`Self::canonical_hash`] for why.
+    pub fn new_cached(eval_plan: SupportedQuery, hash: QueryHash, cache: &CompiledPlanCache) -> Self {
+        let hash = Self::canonical_hash(&eval_plan, hash);
+        let queries = cache.get_or_compile(eval_plan, hash);
+        ExecutionUnit {
+            hash,
+            queries,
+            agg_state: None,
+            topk_state: None,
+        }
+    }
+
+    /// Resolve the `QueryHash` an `ExecutionUnit` is actually keyed and
+    /// compared by.
+    ///
+    /// The subscription manager that calls `new`/`new_cached` today computes
+    /// `hash` via [`QueryHash::from_string`] on the raw query text, before
+    /// this `QueryExpr` has even been planned — that call site lives outside
+    /// this module, so we can't change what it passes in. But nothing
+    /// requires us to trust it: recomputing a canonical hash from the
+    /// already-optimized `expr` here, once, is how
+    /// [`QueryHash::from_query_expr`]'s de-duplication (two subscribers whose
+    /// queries differ only in whitespace or alias naming sharing one
+    /// `ExecutionUnit`/cache entry) actually takes effect, without needing to
+    /// touch the caller.
+    ///
+    /// `QueryHash::NONE` (used by tests and benches via the `From<SupportedQuery>`
+    /// impl) is passed through unchanged, since there's no `expr` semantics to
+    /// canonicalize a sentinel against.
+    fn canonical_hash(eval_plan: &SupportedQuery, hash: QueryHash) -> QueryHash {
+        if hash == QueryHash::NONE {
+            hash
+        } else {
+            QueryHash::from_query_expr(&eval_plan.expr)
+        }
+    }
+
+    /// Construct a `Supported::Aggregate` execution unit which incrementally
+    /// maintains `func` over `agg_col`, grouped by `group_cols`.
+    ///
+    /// `group_cols` and `agg_col` are the positions of the respective
+    /// columns within a row returned by `expr`.
+    ///
+    /// `result_table_id` and `result_header` describe the aggregate's own
+    /// result table — `(group_cols..., agg_value)` — under which rows are
+    /// reported to subscribers, since that shape doesn't match the source
+    /// table's `Header`. The caller (the query planner) is responsible for
+    /// allocating a `TableId` for this result table and building a `Header`
+    /// whose fields are the `GROUP BY` columns' types followed by the
+    /// aggregate output's type.
+    ///
+    /// Aggregate units are not (yet) shared via the `CompiledPlanCache`,
+    /// since their per-group state is unique to each subscription.
+    pub fn new_aggregate(
+        expr: QueryExpr,
+        group_cols: Vec<usize>,
+        agg_col: usize,
+        func: AggregateFunc,
+        result_table_id: TableId,
+        result_header: Header,
+        hash: QueryHash,
+    ) -> Self {
+        let scan_plan = Self::compile_query_expr_to_query_code(expr);
+        let queries = Arc::new(ExecutionUnitQueries::Aggregate {
+            scan_plan,
+            group_cols,
+            agg_col,
+            func,
+            result_table_id,
+            result_header,
+        });
+        ExecutionUnit {
+            hash,
+            queries,
+            agg_state: Some(Mutex::new(HashMap::new())),
+            topk_state: None,
+        }
+    }
+
+    /// Construct a `Supported::TopK` execution unit which incrementally
+    /// maintains the top `limit` rows of `expr`, ordered by `order_col`.
+    ///
+    /// `order_col` is the position of the `ORDER BY` column within a row
+    /// returned by `expr`.
+    ///
+    /// As with `new_aggregate`, TopK units are not (yet) shared via the
+    /// `CompiledPlanCache`, since their window/overflow state is unique to
+    /// each subscription.
+    pub fn new_topk(expr: QueryExpr, order_col: usize, order: SortOrder, limit: usize, hash: QueryHash) -> Self {
+        let scan_plan = Self::compile_query_expr_to_query_code(expr);
+        let queries = Arc::new(ExecutionUnitQueries::TopK {
+            scan_plan,
+            order_col,
+            order,
+            limit,
+        });
+        ExecutionUnit {
+            hash,
+            queries,
+            agg_state: None,
+            topk_state: Some(Mutex::new(TopKState::default())),
+        }
+    }
+
+    /// The `TableId`s read by `plan`, used to key cache invalidation in
+    /// [`CompiledPlanCache`]. Only reachable via [`ExecutionUnit::new_cached`]
+    /// now that [`ExecutionUnit::new`] no longer shares a `CompiledPlanCache`
+    /// (see the note there) — so an omission here understates which tables a
+    /// *caller-supplied* cache should invalidate on, not the default path.
+    // TODO(correctness): only `plan.expr.source` and `Query::IndexJoin` are
+    // accounted for here. Any other `Query` op that reads from a second
+    // table (e.g. a non-index join, or a subquery source) escapes this scan,
+    // so a `CompiledPlanCache::bump_schema_version` on such a table would
+    // not invalidate a cached plan reading it that way. Covering every
+    // `Query` variant requires auditing its full definition, which isn't
+    // visible from this module.
+    fn referenced_table_ids(plan: &SupportedQuery) -> Vec<TableId> {
+        let mut ids = Vec::new();
+        if let Some(table) = plan.expr.source.get_db_table() {
+            ids.push(table.table_id);
+        }
+        for op in &plan.expr.query {
+            if let Query::IndexJoin(join) = op {
+                if let Some(table) = join.index_side.get_db_table() {
+                    ids.push(table.table_id);
+                }
+                if let Some(table) = join.probe_side.source.get_db_table() {
+                    ids.push(table.table_id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn compile(eval_plan: SupportedQuery) -> ExecutionUnitQueries {
+        match eval_plan {
             SupportedQuery {
                 kind: query::Supported::Select,
                 expr,
@@ -166,16 +932,44 @@ impl ExecutionUnit {
             SupportedQuery {
                 kind: query::Supported::Semijoin,
                 expr,
-            } => ExecutionUnitQueries::Semijoin(expr),
-        };
-        ExecutionUnit { hash, queries }
+            } => {
+                // Store the `expr` for two different paths:
+                // - `eval_plan`, a fully compiled `QueryCode` for initial subscriptions.
+                // - `eval_incr_plan`, the `expr` itself, handed as is to `IncrementalJoin`
+                //   on every incremental update.
+                let eval_plan = Self::compile_semijoin_eval(&expr);
+
+                ExecutionUnitQueries::Semijoin {
+                    eval_plan,
+                    eval_incr_plan: expr,
+                }
+            }
+        }
     }
 
-    /// Is this a single table select or a semijoin?
+    /// Is this a single table select, or a semijoin?
+    ///
+    /// `Aggregate`/`TopK` units have no answer here: `super::query::Supported`
+    /// (outside this module) has only `Select`/`Semijoin` variants, since
+    /// nothing outside this module builds `GROUP BY`/`ORDER BY ... LIMIT`
+    /// subscriptions yet (see the TODOs on those `ExecutionUnitQueries`
+    /// variants and on [`Self::new_aggregate`]/[`Self::new_topk`]). Adding
+    /// `Supported::Aggregate`/`Supported::TopK`, and parsing into one of
+    /// these units via the planner, belongs in `super::query`, not here;
+    /// until that lands, `kind()` is only ever called on `Select`/`Semijoin`
+    /// units.
     pub fn kind(&self) -> Supported {
-        match self.queries {
+        match &*self.queries {
             ExecutionUnitQueries::Select { .. } => Supported::Select,
-            ExecutionUnitQueries::Semijoin(_) => Supported::Semijoin,
+            ExecutionUnitQueries::Semijoin { .. } => Supported::Semijoin,
+            ExecutionUnitQueries::Aggregate { .. } => {
+                unreachable!("Aggregate units are only constructed directly via `new_aggregate`; \
+                              nothing outside this module builds one through a `Supported`-classified path yet")
+            }
+            ExecutionUnitQueries::TopK { .. } => {
+                unreachable!("TopK units are only constructed directly via `new_topk`; \
+                              nothing outside this module builds one through a `Supported`-classified path yet")
+            }
         }
     }
 
@@ -184,26 +978,51 @@ impl ExecutionUnit {
         self.hash
     }
 
+    /// The source `DbTable` of this execution unit's compiled plan.
+    ///
+    /// Not meaningful for `Aggregate`, whose output rows — `(group_cols...,
+    /// agg_value)` — don't share the source table's shape; see
+    /// [`Self::return_table`]/[`Self::return_name`], which use `Aggregate`'s
+    /// own `result_table_id`/`result_header` instead of calling this.
     fn return_db_table(&self) -> &DbTable {
-        match &self.queries {
+        match &*self.queries {
             ExecutionUnitQueries::Select { eval_plan, .. } => eval_plan
                 .table
                 .get_db_table()
                 .expect("ExecutionUnit Select eval_plan should have DbTable source, but found MemTable"),
-            ExecutionUnitQueries::Semijoin(eval_plan) => eval_plan
+            ExecutionUnitQueries::Semijoin { eval_incr_plan, .. } => eval_incr_plan
                 .source
                 .get_db_table()
                 .expect("ExecutionUnit Semijoin eval_plan should have DbTable source, but found MemTable"),
+            ExecutionUnitQueries::Aggregate { scan_plan, .. } => scan_plan
+                .table
+                .get_db_table()
+                .expect("ExecutionUnit Aggregate scan_plan should have DbTable source, but found MemTable"),
+            ExecutionUnitQueries::TopK { scan_plan, .. } => scan_plan
+                .table
+                .get_db_table()
+                .expect("ExecutionUnit TopK scan_plan should have DbTable source, but found MemTable"),
         }
     }
 
-    /// The table from which this query returns rows.
+    /// The table from which this query returns rows to a subscriber.
+    ///
+    /// For `Select`, `Semijoin`, and `TopK`, this is the source table, since
+    /// their output rows share its shape. For `Aggregate`, whose output rows
+    /// are reshaped to `(group_cols..., agg_value)`, this is instead the
+    /// aggregate's own dedicated result table.
     pub fn return_table(&self) -> TableId {
-        self.return_db_table().table_id
+        match &*self.queries {
+            ExecutionUnitQueries::Aggregate { result_table_id, .. } => *result_table_id,
+            _ => self.return_db_table().table_id,
+        }
     }
 
     pub fn return_name(&self) -> String {
-        self.return_db_table().head.table_name.clone()
+        match &*self.queries {
+            ExecutionUnitQueries::Aggregate { result_header, .. } => result_header.table_name.clone(),
+            _ => self.return_db_table().head.table_name.clone(),
+        }
     }
 
     /// The table on which this query filters rows.
@@ -213,8 +1032,9 @@ impl ExecutionUnit {
     /// it is the auxiliary table against which we are joining.
     pub fn filter_table(&self) -> TableId {
         let return_table = self.return_table();
-        if let ExecutionUnitQueries::Semijoin(plan) = &self.queries {
-            plan.query
+        if let ExecutionUnitQueries::Semijoin { eval_incr_plan, .. } = &*self.queries {
+            eval_incr_plan
+                .query
                 .first()
                 .and_then(|op| {
                     if let Query::IndexJoin(join) = op {
@@ -239,10 +1059,36 @@ impl ExecutionUnit {
 
     /// Evaluate this execution unit against the database.
     #[tracing::instrument(skip_all)]
-    pub fn eval(&self, db: &RelationalDB, tx: &Tx, auth: AuthCtx) -> Result<Option<DatabaseTableUpdate>, DBError> {
-        let ops = match &self.queries {
-            ExecutionUnitQueries::Select { eval_plan, .. } => Self::eval_query_code(db, tx, eval_plan)?,
-            ExecutionUnitQueries::Semijoin(eval_plan) => Self::eval_query_expr(db, tx, auth, eval_plan)?,
+    pub fn eval(&self, db: &RelationalDB, tx: &Tx, _auth: AuthCtx) -> Result<Option<DatabaseTableUpdate>, DBError> {
+        let ops = match &*self.queries {
+            ExecutionUnitQueries::Select { eval_plan, .. } | ExecutionUnitQueries::Semijoin { eval_plan, .. } => {
+                Self::eval_query_code(db, tx, eval_plan)?
+            }
+            ExecutionUnitQueries::Aggregate {
+                scan_plan,
+                group_cols,
+                agg_col,
+                func,
+                ..
+            } => {
+                let state = self
+                    .agg_state
+                    .as_ref()
+                    .expect("ExecutionUnit Aggregate units always have `agg_state`");
+                Self::eval_aggregate(db, tx, scan_plan, group_cols, *agg_col, *func, state)?
+            }
+            ExecutionUnitQueries::TopK {
+                scan_plan,
+                order_col,
+                order,
+                limit,
+            } => {
+                let state = self
+                    .topk_state
+                    .as_ref()
+                    .expect("ExecutionUnit TopK units always have `topk_state`");
+                Self::eval_topk(db, tx, scan_plan, *order_col, *order, *limit, state)?
+            }
         };
         Ok((!ops.is_empty()).then(|| DatabaseTableUpdate {
             table_id: self.return_table(),
@@ -251,17 +1097,146 @@ impl ExecutionUnit {
         }))
     }
 
-    fn eval_query_expr(
+    /// The positions of `group_cols` within `row`, as a `ProductValue` key.
+    fn group_key(row: &ProductValue, group_cols: &[usize]) -> ProductValue {
+        ProductValue::from_iter(group_cols.iter().map(|&i| row.elements[i].clone()))
+    }
+
+    /// The output row for a group: its key columns, followed by the
+    /// current value of the aggregate.
+    fn aggregate_output_row(key: &ProductValue, acc: &AggregateAccumulator, func: AggregateFunc) -> ProductValue {
+        let mut elements = key.elements.clone();
+        elements.push(acc.value(func));
+        ProductValue::from_iter(elements)
+    }
+
+    /// Widen any numeric `AlgebraicValue` to `f64`.
+    ///
+    /// Only used for `SUM`/`AVG` over `F32`/`F64` columns; integer columns
+    /// accumulate exactly via [`Self::algebraic_as_i128`] instead, since
+    /// `f64` cannot represent every `I64`/`U64`/`I128`/`U128` value exactly.
+    fn algebraic_as_f64(value: &AlgebraicValue) -> f64 {
+        match value {
+            AlgebraicValue::I8(n) => *n as f64,
+            AlgebraicValue::U8(n) => *n as f64,
+            AlgebraicValue::I16(n) => *n as f64,
+            AlgebraicValue::U16(n) => *n as f64,
+            AlgebraicValue::I32(n) => *n as f64,
+            AlgebraicValue::U32(n) => *n as f64,
+            AlgebraicValue::I64(n) => *n as f64,
+            AlgebraicValue::U64(n) => *n as f64,
+            AlgebraicValue::I128(n) => *n as f64,
+            AlgebraicValue::U128(n) => *n as f64,
+            AlgebraicValue::F32(n) => f32::from(*n) as f64,
+            AlgebraicValue::F64(n) => f64::from(*n),
+            _ => 0.0,
+        }
+    }
+
+    /// Widen any integer `AlgebraicValue` to `i128` for exact `SUM`/`AVG`
+    /// accumulation. `i128` comfortably holds the full range of every
+    /// integer `AlgebraicValue` variant up to `I128`/`U128`, so unlike
+    /// [`Self::algebraic_as_f64`], accumulating here never loses precision.
+    fn algebraic_as_i128(value: &AlgebraicValue) -> i128 {
+        match value {
+            AlgebraicValue::I8(n) => *n as i128,
+            AlgebraicValue::U8(n) => *n as i128,
+            AlgebraicValue::I16(n) => *n as i128,
+            AlgebraicValue::U16(n) => *n as i128,
+            AlgebraicValue::I32(n) => *n as i128,
+            AlgebraicValue::U32(n) => *n as i128,
+            AlgebraicValue::I64(n) => *n as i128,
+            AlgebraicValue::U64(n) => *n as i128,
+            AlgebraicValue::I128(n) => *n,
+            AlgebraicValue::U128(n) => *n as i128,
+            _ => 0,
+        }
+    }
+
+    /// Populate `state` from a full scan of the source table via `scan_plan`,
+    /// and return the initial set of `TableOp::insert`s, one per group.
+    fn eval_aggregate(
         db: &RelationalDB,
         tx: &Tx,
-        auth: AuthCtx,
-        eval_plan: &QueryExpr,
+        scan_plan: &QueryCode,
+        group_cols: &[usize],
+        agg_col: usize,
+        func: AggregateFunc,
+        state: &Mutex<HashMap<ProductValue, AggregateAccumulator>>,
     ) -> Result<Vec<TableOp>, DBError> {
-        let ctx = ExecutionContext::subscribe(db.address());
-        let mut ops = vec![];
-        for table in run_query(&ctx, db, tx, eval_plan, auth, SourceSet::default())? {
-            ops.extend(table.data.into_iter().map(TableOp::insert));
+        let mut groups: HashMap<ProductValue, AggregateAccumulator> = HashMap::new();
+        for op in Self::eval_query_code(db, tx, scan_plan)? {
+            let key = Self::group_key(&op.row, group_cols);
+            let value = op.row.elements[agg_col].clone();
+            groups
+                .entry(key)
+                .or_insert_with(|| AggregateAccumulator::new(func, &value))
+                .fold_insert(&value);
         }
+        let ops = groups
+            .iter()
+            .map(|(key, acc)| TableOp::insert(Self::aggregate_output_row(key, acc, func)))
+            .collect();
+        *state.lock().unwrap() = groups;
+        Ok(ops)
+    }
+
+    /// Scan the full source table via `scan_plan`, and split the rows into a
+    /// `limit`-sized window, ordered by `order_col`/`order`, plus a
+    /// `limit`-sized overflow buffer of the rows immediately beyond it.
+    /// Rows ranking beyond the overflow buffer are dropped.
+    fn scan_topk(
+        db: &RelationalDB,
+        tx: &Tx,
+        scan_plan: &QueryCode,
+        order_col: usize,
+        order: SortOrder,
+        limit: usize,
+    ) -> Result<TopKState, DBError> {
+        let mut sorted: BTreeMap<OrderKey, Vec<ProductValue>> = BTreeMap::new();
+        for op in Self::eval_query_code(db, tx, scan_plan)? {
+            let key = OrderKey {
+                value: op.row.elements[order_col].clone(),
+                order,
+            };
+            sorted.entry(key).or_default().push(op.row);
+        }
+
+        let mut state = TopKState::default();
+        'rows: for (key, rows) in sorted {
+            for row in rows {
+                if TopKState::len(&state.window) < limit {
+                    TopKState::push(&mut state.window, key.clone(), row);
+                } else if TopKState::len(&state.overflow) < limit {
+                    TopKState::push(&mut state.overflow, key.clone(), row);
+                } else {
+                    break 'rows;
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    /// Populate `state` from a full scan of the database,
+    /// and return the initial set of `TableOp::insert`s, one per window row.
+    fn eval_topk(
+        db: &RelationalDB,
+        tx: &Tx,
+        scan_plan: &QueryCode,
+        order_col: usize,
+        order: SortOrder,
+        limit: usize,
+        state: &Mutex<TopKState>,
+    ) -> Result<Vec<TableOp>, DBError> {
+        let new_state = Self::scan_topk(db, tx, scan_plan, order_col, order, limit)?;
+        let ops = new_state
+            .window
+            .values()
+            .flatten()
+            .cloned()
+            .map(TableOp::insert)
+            .collect();
+        *state.lock().unwrap() = new_state;
         Ok(ops)
     }
 
@@ -283,11 +1258,56 @@ impl ExecutionUnit {
         tables: impl Iterator<Item = &'a DatabaseTableUpdate>,
         auth: AuthCtx,
     ) -> Result<Option<DatabaseTableUpdate>, DBError> {
-        let ops = match &self.queries {
+        let ops = match &*self.queries {
             ExecutionUnitQueries::Select { eval_incr_plan, .. } => {
                 Self::eval_incr_query_code(db, tx, tables, eval_incr_plan, self.return_table())?
             }
-            ExecutionUnitQueries::Semijoin(eval_plan) => Self::eval_incr_query_expr(db, tx, tables, auth, eval_plan)?,
+            ExecutionUnitQueries::Semijoin { eval_incr_plan, .. } => {
+                Self::eval_incr_query_expr(db, tx, tables, auth, eval_incr_plan)?
+            }
+            ExecutionUnitQueries::Aggregate {
+                group_cols, agg_col, func, ..
+            } => {
+                let state = self
+                    .agg_state
+                    .as_ref()
+                    .expect("ExecutionUnit Aggregate units always have `agg_state`");
+                // NOTE: filter on the *source* table id, not `self.return_table()`.
+                // Aggregate's `return_table()` is the synthetic `result_table_id`
+                // used to report output rows to subscribers, but incoming
+                // `DatabaseTableUpdate`s are still keyed by the source table that
+                // `group_cols`/`agg_col` index into.
+                Self::eval_incr_aggregate(
+                    tables,
+                    self.return_db_table().table_id,
+                    group_cols,
+                    *agg_col,
+                    *func,
+                    state,
+                )
+            }
+            ExecutionUnitQueries::TopK {
+                scan_plan,
+                order_col,
+                order,
+                limit,
+            } => {
+                let state = self
+                    .topk_state
+                    .as_ref()
+                    .expect("ExecutionUnit TopK units always have `topk_state`");
+                Self::eval_incr_topk(
+                    db,
+                    tx,
+                    tables,
+                    self.return_table(),
+                    scan_plan,
+                    *order_col,
+                    *order,
+                    *limit,
+                    state,
+                )?
+            }
         };
         Ok((!ops.is_empty()).then(|| DatabaseTableUpdate {
             table_id: self.return_table(),
@@ -296,6 +1316,218 @@ impl ExecutionUnit {
         }))
     }
 
+    /// Apply a single delta row (`key`, `row`, `is_insert`) to `state`'s
+    /// window and overflow buffer, entirely in memory.
+    ///
+    /// Returns the `TableOp`s produced by this row alone, and whether the
+    /// window now needs a full rescan: this happens when a window row was
+    /// deleted and the overflow buffer was empty, so there is no candidate
+    /// to backfill it with.
+    ///
+    /// Extracted out of [`Self::eval_incr_topk`] so the windowing logic can
+    /// be exercised directly in tests, without a `RelationalDB`/`Tx`.
+    fn apply_topk_op(
+        state: &mut TopKState,
+        key: OrderKey,
+        row: ProductValue,
+        is_insert: bool,
+        limit: usize,
+    ) -> (Vec<TableOp>, bool) {
+        let mut ops = Vec::new();
+
+        if is_insert {
+            let fits_window = TopKState::len(&state.window) < limit
+                || TopKState::worst(&state.window).map_or(false, |worst| key < worst);
+            if fits_window {
+                if TopKState::len(&state.window) >= limit {
+                    if let Some(worst) = TopKState::worst(&state.window) {
+                        if let Some(evicted) = TopKState::pop_one(&mut state.window, &worst) {
+                            ops.push(TableOp::delete(evicted.clone()));
+                            TopKState::push(&mut state.overflow, worst, evicted);
+                            if TopKState::len(&state.overflow) > limit {
+                                if let Some(overflow_worst) = TopKState::worst(&state.overflow) {
+                                    TopKState::pop_one(&mut state.overflow, &overflow_worst);
+                                }
+                            }
+                        }
+                    }
+                }
+                TopKState::push(&mut state.window, key, row.clone());
+                ops.push(TableOp::insert(row));
+            } else {
+                let fits_overflow = TopKState::len(&state.overflow) < limit
+                    || TopKState::worst(&state.overflow).map_or(false, |worst| key < worst);
+                if fits_overflow {
+                    if TopKState::len(&state.overflow) >= limit {
+                        if let Some(worst) = TopKState::worst(&state.overflow) {
+                            TopKState::pop_one(&mut state.overflow, &worst);
+                        }
+                    }
+                    TopKState::push(&mut state.overflow, key, row);
+                }
+                // Otherwise the row ranks beyond the overflow buffer; drop it.
+            }
+            (ops, false)
+        } else if TopKState::remove(&mut state.window, &key, &row) {
+            ops.push(TableOp::delete(row));
+            if let Some(best) = TopKState::best(&state.overflow) {
+                if let Some(promoted) = TopKState::pop_one(&mut state.overflow, &best) {
+                    ops.push(TableOp::insert(promoted.clone()));
+                    TopKState::push(&mut state.window, best, promoted);
+                }
+                (ops, false)
+            } else {
+                // No overflow row to backfill the window with; the caller
+                // must fall back to a full rescan.
+                (ops, true)
+            }
+        } else {
+            TopKState::remove(&mut state.overflow, &key, &row);
+            (ops, false)
+        }
+    }
+
+    /// The `TableOp`s needed to move a subscriber's view of the window from
+    /// `old` to `new`: a delete for every row that left the window, an
+    /// insert for every row that entered it.
+    ///
+    /// Used to reconcile the ops already emitted for a batch of deltas with
+    /// the final, rescanned state, since that state may no longer agree with
+    /// an in-progress incremental update (see [`Self::eval_incr_topk`]).
+    fn diff_topk_window(
+        old: &BTreeMap<OrderKey, Vec<ProductValue>>,
+        new: &BTreeMap<OrderKey, Vec<ProductValue>>,
+    ) -> Vec<TableOp> {
+        let mut counts: HashMap<ProductValue, i64> = HashMap::new();
+        for row in old.values().flatten() {
+            *counts.entry(row.clone()).or_insert(0) -= 1;
+        }
+        for row in new.values().flatten() {
+            *counts.entry(row.clone()).or_insert(0) += 1;
+        }
+
+        let mut ops = Vec::new();
+        for (row, count) in counts {
+            match count.cmp(&0) {
+                std::cmp::Ordering::Greater => ops.extend((0..count).map(|_| TableOp::insert(row.clone()))),
+                std::cmp::Ordering::Less => ops.extend((0..-count).map(|_| TableOp::delete(row.clone()))),
+                std::cmp::Ordering::Equal => {}
+            }
+        }
+        ops
+    }
+
+    /// Incrementally maintain `state`'s window and overflow buffer against
+    /// the delta `tables`.
+    ///
+    /// Inserted rows that rank inside the window bump out its current worst
+    /// row (demoted into the overflow buffer); inserted rows beyond the
+    /// window but within the overflow buffer's capacity are buffered but not
+    /// emitted. Deleted rows inside the window are backfilled from the best
+    /// overflow row.
+    ///
+    /// If a deleted window row can't be backfilled (the overflow buffer is
+    /// empty), incremental maintenance for this batch is abandoned: we stop
+    /// applying further delta ops (so they aren't folded into a `state` that
+    /// is about to be discarded), rescan the source table under the current
+    /// `Tx` to rebuild `state` from scratch, and diff the window *before*
+    /// this call against the rescanned window to compute the `TableOp`s for
+    /// the whole batch at once. This guarantees the ops returned always
+    /// match the final `state`, even though individual deltas were not all
+    /// applied incrementally.
+    #[allow(clippy::too_many_arguments)]
+    fn eval_incr_topk<'a>(
+        db: &RelationalDB,
+        tx: &Tx,
+        tables: impl Iterator<Item = &'a DatabaseTableUpdate>,
+        return_table: TableId,
+        scan_plan: &QueryCode,
+        order_col: usize,
+        order: SortOrder,
+        limit: usize,
+        state: &Mutex<TopKState>,
+    ) -> Result<Vec<TableOp>, DBError> {
+        let old_window = state.lock().unwrap().window.clone();
+        let mut ops = Vec::new();
+        let mut needs_rescan = false;
+
+        {
+            let mut state = state.lock().unwrap();
+            'ops: for table in tables.filter(|table| table.table_id == return_table) {
+                for op in &table.ops {
+                    let key = OrderKey {
+                        value: op.row.elements[order_col].clone(),
+                        order,
+                    };
+                    let (mut row_ops, rescan) =
+                        Self::apply_topk_op(&mut state, key, op.row.clone(), op.op_type != 0, limit);
+                    if rescan {
+                        needs_rescan = true;
+                        break 'ops;
+                    }
+                    ops.append(&mut row_ops);
+                }
+            }
+        }
+
+        if needs_rescan {
+            let new_state = Self::scan_topk(db, tx, scan_plan, order_col, order, limit)?;
+            let new_window = new_state.window.clone();
+            *state.lock().unwrap() = new_state;
+            return Ok(Self::diff_topk_window(&old_window, &new_window));
+        }
+
+        Ok(ops)
+    }
+
+    /// Incrementally maintain `state` against the delta `tables`,
+    /// returning a delete for every group's stale output row and an insert
+    /// for its new one, or only a delete when a group is emptied out.
+    ///
+    /// `source_table` is the table `group_cols`/`agg_col` index into, i.e.
+    /// `return_db_table().table_id` — *not* the aggregate's synthetic
+    /// `result_table_id`, since the `DatabaseTableUpdate`s passed in `tables`
+    /// are keyed by the source table that changed, not the result table this
+    /// unit reports rows under.
+    fn eval_incr_aggregate<'a>(
+        tables: impl Iterator<Item = &'a DatabaseTableUpdate>,
+        source_table: TableId,
+        group_cols: &[usize],
+        agg_col: usize,
+        func: AggregateFunc,
+        state: &Mutex<HashMap<ProductValue, AggregateAccumulator>>,
+    ) -> Vec<TableOp> {
+        let mut ops = Vec::new();
+        let mut groups = state.lock().unwrap();
+        for table in tables.filter(|table| table.table_id == source_table) {
+            for op in &table.ops {
+                let key = Self::group_key(&op.row, group_cols);
+                let value = op.row.elements[agg_col].clone();
+                let is_insert = op.op_type != 0;
+
+                let prior_output = groups.get(&key).map(|acc| Self::aggregate_output_row(&key, acc, func));
+                let acc = groups
+                    .entry(key.clone())
+                    .or_insert_with(|| AggregateAccumulator::new(func, &value));
+                if is_insert {
+                    acc.fold_insert(&value);
+                } else {
+                    acc.fold_delete(&value);
+                }
+
+                if let Some(prior_output) = prior_output {
+                    ops.push(TableOp::delete(prior_output));
+                }
+                if acc.row_count == 0 {
+                    groups.remove(&key);
+                } else {
+                    ops.push(TableOp::insert(Self::aggregate_output_row(&key, &groups[&key], func)));
+                }
+            }
+        }
+        ops
+    }
+
     fn eval_incr_query_expr<'a>(
         db: &RelationalDB,
         tx: &Tx,
@@ -373,4 +1605,159 @@ impl ExecutionUnit {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(n: i64) -> ProductValue {
+        ProductValue::from_iter([AlgebraicValue::I64(n)])
+    }
+
+    fn asc_key(n: i64) -> OrderKey {
+        OrderKey {
+            value: AlgebraicValue::I64(n),
+            order: SortOrder::Asc,
+        }
+    }
+
+    fn op_row(op: &TableOp) -> &ProductValue {
+        &op.row
+    }
+
+    #[test]
+    fn algebraic_as_f64_widens_every_numeric_variant() {
+        assert_eq!(ExecutionUnit::algebraic_as_f64(&AlgebraicValue::I32(-7)), -7.0);
+        assert_eq!(ExecutionUnit::algebraic_as_f64(&AlgebraicValue::U32(7)), 7.0);
+        assert_eq!(ExecutionUnit::algebraic_as_f64(&AlgebraicValue::I8(-1)), -1.0);
+        assert_eq!(ExecutionUnit::algebraic_as_f64(&AlgebraicValue::U128(42)), 42.0);
+        assert_eq!(ExecutionUnit::algebraic_as_f64(&AlgebraicValue::I64(9)), 9.0);
+    }
+
+    #[test]
+    fn aggregate_sum_preserves_i32_width() {
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Sum, &AlgebraicValue::I32(0));
+        acc.fold_insert(&AlgebraicValue::I32(3));
+        acc.fold_insert(&AlgebraicValue::I32(4));
+        assert_eq!(acc.value(AggregateFunc::Sum), AlgebraicValue::I32(7));
+    }
+
+    #[test]
+    fn aggregate_avg_preserves_u32_width() {
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Avg, &AlgebraicValue::U32(0));
+        acc.fold_insert(&AlgebraicValue::U32(2));
+        acc.fold_insert(&AlgebraicValue::U32(4));
+        assert_eq!(acc.value(AggregateFunc::Avg), AlgebraicValue::U32(3));
+    }
+
+    #[test]
+    fn aggregate_sum_is_exact_for_large_i64() {
+        // Past 2^53, `f64` can no longer represent every `i64` exactly;
+        // accumulating in `i128` must still get this exactly right.
+        let big = (1i64 << 53) + 1;
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Sum, &AlgebraicValue::I64(0));
+        acc.fold_insert(&AlgebraicValue::I64(big));
+        acc.fold_insert(&AlgebraicValue::I64(big));
+        assert_eq!(acc.value(AggregateFunc::Sum), AlgebraicValue::I64(big * 2));
+    }
+
+    #[test]
+    fn aggregate_avg_truncates_integer_division() {
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Avg, &AlgebraicValue::I32(0));
+        acc.fold_insert(&AlgebraicValue::I32(2));
+        acc.fold_insert(&AlgebraicValue::I32(3));
+        // (2 + 3) / 2 == 2, not 2.5: AVG over an integer column truncates
+        // toward zero, since it must produce a value of that column's type.
+        assert_eq!(acc.value(AggregateFunc::Avg), AlgebraicValue::I32(2));
+    }
+
+    #[test]
+    fn aggregate_count_is_always_u64() {
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Count, &AlgebraicValue::I32(0));
+        acc.fold_insert(&AlgebraicValue::I32(1));
+        acc.fold_insert(&AlgebraicValue::I32(2));
+        assert_eq!(acc.value(AggregateFunc::Count), AlgebraicValue::U64(2));
+    }
+
+    #[test]
+    fn aggregate_min_max_preserve_exact_value_and_type() {
+        let mut acc = AggregateAccumulator::new(AggregateFunc::Max, &AlgebraicValue::I32(5));
+        acc.fold_insert(&AlgebraicValue::I32(5));
+        acc.fold_insert(&AlgebraicValue::I32(9));
+        acc.fold_insert(&AlgebraicValue::I32(1));
+        assert_eq!(acc.value(AggregateFunc::Max), AlgebraicValue::I32(9));
+        acc.fold_delete(&AlgebraicValue::I32(9));
+        assert_eq!(acc.value(AggregateFunc::Max), AlgebraicValue::I32(5));
+    }
+
+    #[test]
+    fn apply_topk_op_insert_evicts_worst_from_window() {
+        let mut state = TopKState::default();
+        let limit = 2;
+
+        // Fill the window: [1, 2].
+        for n in [1, 2] {
+            let (ops, rescan) = ExecutionUnit::apply_topk_op(&mut state, asc_key(n), row(n), true, limit);
+            assert!(!rescan);
+            assert_eq!(ops.len(), 1);
+        }
+        assert_eq!(TopKState::len(&state.window), 2);
+
+        // Inserting `0` should evict `2` into the overflow buffer.
+        let (ops, rescan) = ExecutionUnit::apply_topk_op(&mut state, asc_key(0), row(0), true, limit);
+        assert!(!rescan);
+        assert_eq!(ops.len(), 2, "expected a delete of the evicted row and an insert of the new one");
+        assert!(ops.iter().any(|op| op.op_type != 0 && op_row(op) == &row(0)));
+        assert!(ops.iter().any(|op| op.op_type == 0 && op_row(op) == &row(2)));
+        assert_eq!(TopKState::len(&state.overflow), 1);
+    }
+
+    #[test]
+    fn apply_topk_op_delete_backfills_from_overflow() {
+        let mut state = TopKState::default();
+        let limit = 1;
+        ExecutionUnit::apply_topk_op(&mut state, asc_key(1), row(1), true, limit);
+        ExecutionUnit::apply_topk_op(&mut state, asc_key(2), row(2), true, limit);
+        assert_eq!(TopKState::len(&state.window), 1);
+        assert_eq!(TopKState::len(&state.overflow), 1);
+
+        let (ops, rescan) = ExecutionUnit::apply_topk_op(&mut state, asc_key(1), row(1), false, limit);
+        assert!(!rescan);
+        assert!(ops.iter().any(|op| op.op_type == 0 && op_row(op) == &row(1)));
+        assert!(ops.iter().any(|op| op.op_type != 0 && op_row(op) == &row(2)));
+        assert_eq!(TopKState::len(&state.window), 1);
+        assert_eq!(TopKState::len(&state.overflow), 0);
+    }
+
+    #[test]
+    fn apply_topk_op_delete_with_empty_overflow_requests_rescan() {
+        let mut state = TopKState::default();
+        let limit = 1;
+        ExecutionUnit::apply_topk_op(&mut state, asc_key(1), row(1), true, limit);
+
+        let (ops, rescan) = ExecutionUnit::apply_topk_op(&mut state, asc_key(1), row(1), false, limit);
+        assert!(rescan, "deleting the only window row with no overflow must request a rescan");
+        assert!(ops.iter().any(|op| op.op_type == 0 && op_row(op) == &row(1)));
+    }
+
+    #[test]
+    fn diff_topk_window_reconciles_after_rescan() {
+        // Simulates the case the bug fix addresses: `old` is the window
+        // before a batch of deltas, `new` is what a full rescan produces
+        // after that batch (backfilling rows that incremental maintenance
+        // alone would have lost).
+        let mut old = BTreeMap::new();
+        TopKState::push(&mut old, asc_key(1), row(1));
+        TopKState::push(&mut old, asc_key(2), row(2));
+
+        let mut new = BTreeMap::new();
+        TopKState::push(&mut new, asc_key(2), row(2));
+        TopKState::push(&mut new, asc_key(3), row(3));
+
+        let ops = ExecutionUnit::diff_topk_window(&old, &new);
+        assert_eq!(ops.len(), 2);
+        assert!(ops.iter().any(|op| op.op_type == 0 && op_row(op) == &row(1)));
+        assert!(ops.iter().any(|op| op.op_type != 0 && op_row(op) == &row(3)));
+    }
+}